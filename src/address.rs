@@ -1,5 +1,5 @@
 /// An M-Bus address field.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum Address {
   Unconfigured,
@@ -20,3 +20,15 @@ impl From<u8> for Address {
     }
   }
 }
+
+impl From<Address> for u8 {
+  fn from(address: Address) -> Self {
+    match address {
+      Address::Unconfigured => 0,
+      Address::Configured(address) => address,
+      Address::Reserved => 251,
+      Address::AddressingPerformedInNetworkLayer => 253,
+      Address::Broadcast => 254,
+    }
+  }
+}
@@ -0,0 +1,17 @@
+use crate::{Error, Telegram};
+
+/// A blocking master/slave transport.
+///
+/// Implemented by [`MBusMaster`](crate::MBusMaster) for any blocking byte transport; callers
+/// who want to drive the bus themselves (e.g. over a mocked transport in tests) can implement
+/// this directly instead.
+///
+/// There is no non-blocking counterpart yet; one is worth adding once there's a concrete
+/// async transport to implement it against.
+pub trait SyncClient {
+  /// Sends `telegram` over the bus, blocking until it has been fully written.
+  fn send(&mut self, telegram: &Telegram<'_>) -> Result<(), Error>;
+
+  /// Blocks until a complete telegram has been received, parsing it into `buf`.
+  fn receive<'buf>(&mut self, buf: &'buf mut [u8]) -> Result<Telegram<'buf>, Error>;
+}
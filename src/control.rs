@@ -1,7 +1,7 @@
 use core::convert::TryFrom;
 
 /// An M-Bus control field.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum Control {
   /// Initialization of slave.
@@ -36,3 +36,21 @@ impl TryFrom<u8> for Control {
     })
   }
 }
+
+impl From<Control> for u8 {
+  fn from(control: Control) -> Self {
+    match control {
+      Control::SndNke => 0b01000000,
+      Control::SndUd { fcb: false } => 0b01010011,
+      Control::SndUd { fcb: true } => 0b01110011,
+      Control::ReqUd1 { fcb: false } => 0b01011011,
+      Control::ReqUd1 { fcb: true } => 0b01111011,
+      Control::ReqUd2 { fcb: false } => 0b01011010,
+      Control::ReqUd2 { fcb: true } => 0b01111010,
+      Control::RspUd { acd: false, dfc: false } => 0b00001000,
+      Control::RspUd { acd: false, dfc: true } => 0b00011000,
+      Control::RspUd { acd: true, dfc: false } => 0b00101000,
+      Control::RspUd { acd: true, dfc: true } => 0b00111000,
+    }
+  }
+}
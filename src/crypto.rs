@@ -0,0 +1,131 @@
+use crate::Error;
+
+/// An AES-128-CBC implementation usable to decrypt M-Bus Security Mode 5 payloads.
+///
+/// Implemented by the `rustcrypto`, `mbedtls` and `openssl` feature backends so callers can
+/// pick whichever AES implementation suits their target, while [`Telegram::decrypt`] stays
+/// generic over the choice.
+///
+/// [`Telegram::decrypt`]: crate::Telegram::decrypt
+pub trait Aes128Cbc {
+  /// Decrypts `data` in place using CBC mode with the given `key` and `iv`.
+  ///
+  /// `data.len()` must be a non-zero multiple of the AES block size (16 bytes).
+  fn decrypt(&self, key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error>;
+}
+
+#[cfg(feature = "rustcrypto")]
+mod rustcrypto_backend {
+  use cbc::cipher::{BlockDecryptMut, KeyIvInit, block_padding::NoPadding};
+
+  use super::Aes128Cbc;
+  use crate::Error;
+
+  /// [`Aes128Cbc`] backed by the pure-Rust `aes`/`cbc` crates.
+  #[derive(Debug, Clone, Copy, Default)]
+  pub struct RustCrypto;
+
+  impl Aes128Cbc for RustCrypto {
+    fn decrypt(&self, key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error> {
+      cbc::Decryptor::<aes::Aes128>::new(key.into(), iv.into())
+        .decrypt_padded_mut::<NoPadding>(data)
+        .map_err(|_| Error::DecryptionFailed)?;
+
+      Ok(())
+    }
+  }
+}
+#[cfg(feature = "rustcrypto")]
+pub use rustcrypto_backend::RustCrypto;
+
+#[cfg(feature = "mbedtls")]
+mod mbedtls_backend {
+  use mbedtls::cipher::{Cipher, Decryption, Traditional, raw::CipherId, raw::CipherMode, raw::CipherPadding};
+
+  use super::Aes128Cbc;
+  use crate::Error;
+
+  /// [`Aes128Cbc`] backed by `mbedtls`.
+  #[derive(Debug, Clone, Copy, Default)]
+  pub struct Mbedtls;
+
+  impl Aes128Cbc for Mbedtls {
+    fn decrypt(&self, key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error> {
+      let mut cipher = Cipher::<Decryption, Traditional, _>::new(CipherId::Aes, CipherMode::CBC, 128)
+        .map_err(|_| Error::DecryptionFailed)?;
+
+      // M-Bus Mode 5 ciphertext is not PKCS7-padded, unlike the default `mbedtls` behavior.
+      cipher.set_padding(CipherPadding::None).map_err(|_| Error::DecryptionFailed)?;
+      let cipher = cipher.set_key_iv(key, iv).map_err(|_| Error::DecryptionFailed)?;
+
+      let mut plaintext = std::vec![0u8; data.len()];
+      let (written, _) = cipher.decrypt(data, &mut plaintext).map_err(|_| Error::DecryptionFailed)?;
+
+      if written != data.len() {
+        return Err(Error::DecryptionFailed)
+      }
+      data.copy_from_slice(&plaintext[..written]);
+
+      Ok(())
+    }
+  }
+}
+#[cfg(feature = "mbedtls")]
+pub use mbedtls_backend::Mbedtls;
+
+#[cfg(feature = "openssl")]
+mod openssl_backend {
+  use openssl::symm::{Cipher, Crypter, Mode};
+
+  use super::Aes128Cbc;
+  use crate::Error;
+
+  /// [`Aes128Cbc`] backed by `openssl`.
+  #[derive(Debug, Clone, Copy, Default)]
+  pub struct OpenSsl;
+
+  impl Aes128Cbc for OpenSsl {
+    fn decrypt(&self, key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error> {
+      let mut crypter = Crypter::new(Cipher::aes_128_cbc(), Mode::Decrypt, key, Some(iv))
+        .map_err(|_| Error::DecryptionFailed)?;
+      crypter.pad(false);
+
+      let mut plaintext = std::vec![0u8; data.len() + Cipher::aes_128_cbc().block_size()];
+      let mut written = crypter.update(data, &mut plaintext).map_err(|_| Error::DecryptionFailed)?;
+      written += crypter.finalize(&mut plaintext[written..]).map_err(|_| Error::DecryptionFailed)?;
+
+      if written != data.len() {
+        return Err(Error::DecryptionFailed)
+      }
+      data.copy_from_slice(&plaintext[..written]);
+
+      Ok(())
+    }
+  }
+}
+#[cfg(feature = "openssl")]
+pub use openssl_backend::OpenSsl;
+
+#[cfg(all(test, feature = "rustcrypto"))]
+mod test {
+  use cbc::cipher::{BlockEncryptMut, KeyIvInit, block_padding::NoPadding};
+
+  use super::*;
+
+  #[test]
+  fn test_rustcrypto_decrypt_roundtrip() {
+    let key = [0x11u8; 16];
+    let iv = [0x22u8; 16];
+    // Decryption-verification marker followed by payload bytes and trailing `0x2f` filler.
+    let plaintext = [0x2f, 0x2f, 0x01, 0x02, 0x03, 0x04, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f];
+
+    let mut data = plaintext;
+    let len = cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+      .encrypt_padded_mut::<NoPadding>(&mut data, plaintext.len())
+      .unwrap()
+      .len();
+
+    RustCrypto.decrypt(&key, &iv, &mut data[..len]).unwrap();
+    assert_eq!(&data[..len], &plaintext[..]);
+  }
+}
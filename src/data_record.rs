@@ -0,0 +1,386 @@
+use crate::Error;
+
+/// Fixed application-layer header preceding the data records in a `LongFrame`'s `user_data`.
+///
+/// Its shape depends on the telegram's control information: `0x72` (and most other CI values)
+/// carries the [`Header::Long`] form with the full secondary address, while `0x7A` carries the
+/// [`Header::Short`] form, which omits it because the secondary address is already known from
+/// the link layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Header {
+  Long {
+    identification_number: [u8; 4],
+    manufacturer: [u8; 2],
+    version: u8,
+    device_type: u8,
+    access_number: u8,
+    status: u8,
+    signature: [u8; 2],
+  },
+  Short {
+    access_number: u8,
+    status: u8,
+    signature: [u8; 2],
+  },
+}
+
+impl Header {
+  /// Control information value for the short header form.
+  const SHORT_CI: u8 = 0x7a;
+
+  pub(crate) const LONG_LEN: usize = 12;
+  pub(crate) const SHORT_LEN: usize = 4;
+
+  pub(crate) fn parse(control_information: u8, input: &[u8]) -> Result<(Self, &[u8]), Error> {
+    if control_information == Self::SHORT_CI {
+      if input.len() < Self::SHORT_LEN {
+        return Err(Error::Incomplete(None))
+      }
+
+      let (header, rest) = input.split_at(Self::SHORT_LEN);
+
+      let header = Self::Short {
+        access_number: header[0],
+        status: header[1],
+        signature: [header[2], header[3]],
+      };
+
+      return Ok((header, rest))
+    }
+
+    if input.len() < Self::LONG_LEN {
+      return Err(Error::Incomplete(None))
+    }
+
+    let (header, rest) = input.split_at(Self::LONG_LEN);
+
+    let header = Self::Long {
+      identification_number: [header[0], header[1], header[2], header[3]],
+      manufacturer: [header[4], header[5]],
+      version: header[6],
+      device_type: header[7],
+      access_number: header[8],
+      status: header[9],
+      signature: [header[10], header[11]],
+    };
+
+    Ok((header, rest))
+  }
+}
+
+/// Physical unit of a [`DataRecord`]'s value, as decoded from its VIF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Unit {
+  /// Energy, in Wh, scaled by the record's exponent.
+  Energy,
+  /// Volume, in m³, scaled by the record's exponent.
+  Volume,
+  /// Manufacturer-specific fabrication number.
+  FabricationNumber,
+  /// VIF byte that does not map to a unit known to this crate.
+  Unknown(u8),
+}
+
+fn decode_vif(vif: u8) -> (Unit, i32) {
+  match vif {
+    0x00..=0x07 => (Unit::Energy, vif as i32 - 3),
+    0x10..=0x17 => (Unit::Volume, (vif as i32 - 0x10) - 6),
+    0x78 => (Unit::FabricationNumber, 0),
+    vif => (Unit::Unknown(vif), 0),
+  }
+}
+
+/// Decoded value of a [`DataRecord`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'ud> {
+  /// No data is carried by this record.
+  None,
+  /// A little-endian signed integer (8/16/24/32/48/64-bit data field).
+  Integer(i64),
+  /// A 32-bit IEEE-754 float (data field `0x5`).
+  Real(f32),
+  /// A BCD-encoded integer (data field `0x9`-`0xC`).
+  Bcd(u64),
+  /// A variable-length value (data field `0xD`, LVAR).
+  LVar(&'ud [u8]),
+}
+
+/// A single decoded M-Bus variable data structure record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRecord<'ud> {
+  pub storage_number: u32,
+  pub tariff: u32,
+  pub subunit: u32,
+  pub value: Value<'ud>,
+  pub unit: Unit,
+  pub exponent: i32,
+}
+
+/// Iterator over the data records following the [`Header`] in a `LongFrame`'s `user_data`.
+#[derive(Debug, Clone)]
+pub struct DataRecords<'ud> {
+  header: Header,
+  remaining: &'ud [u8],
+  done: bool,
+}
+
+impl<'ud> DataRecords<'ud> {
+  /// Parses the fixed [`Header`] from `user_data` and returns an iterator over its records.
+  ///
+  /// `control_information` selects the header's shape (see [`Header`]).
+  pub fn parse(control_information: u8, user_data: &'ud [u8]) -> Result<Self, Error> {
+    let (header, remaining) = Header::parse(control_information, user_data)?;
+    Ok(Self { header, remaining, done: false })
+  }
+
+  /// The fixed header preceding the data records.
+  pub fn header(&self) -> Header {
+    self.header
+  }
+
+  fn parse_record(&mut self) -> Result<DataRecord<'ud>, Error> {
+    let (&dif, rest) = self.remaining.split_first().ok_or(Error::Incomplete(None))?;
+    self.remaining = rest;
+
+    let data_field = dif & 0b0000_1111;
+    let mut storage_number = u32::from((dif >> 6) & 0b1);
+    let mut tariff = 0u32;
+    let mut subunit = 0u32;
+
+    let mut storage_shift = 1;
+    let mut tariff_shift = 0;
+    let mut subunit_shift = 0;
+    let mut more = dif & 0b1000_0000 != 0;
+
+    while more {
+      // `storage_shift`/`tariff_shift`/`subunit_shift` would overflow the `u32` shifts below
+      // if a malformed/malicious telegram chained enough DIFE bytes, so bail out instead.
+      if storage_shift >= u32::BITS || tariff_shift >= u32::BITS || subunit_shift >= u32::BITS {
+        return Err(Error::InvalidFormat)
+      }
+
+      let (&dife, rest) = self.remaining.split_first().ok_or(Error::Incomplete(None))?;
+      self.remaining = rest;
+
+      storage_number |= u32::from(dife & 0b0000_1111) << storage_shift;
+      tariff |= u32::from((dife >> 4) & 0b0000_0011) << tariff_shift;
+      subunit |= u32::from((dife >> 6) & 0b0000_0001) << subunit_shift;
+
+      storage_shift += 4;
+      tariff_shift += 2;
+      subunit_shift += 1;
+      more = dife & 0b1000_0000 != 0;
+    }
+
+    let (&vif, rest) = self.remaining.split_first().ok_or(Error::Incomplete(None))?;
+    self.remaining = rest;
+
+    let mut more = vif & 0b1000_0000 != 0;
+    while more {
+      let (&vife, rest) = self.remaining.split_first().ok_or(Error::Incomplete(None))?;
+      self.remaining = rest;
+      more = vife & 0b1000_0000 != 0;
+    }
+
+    let (unit, exponent) = decode_vif(vif & 0b0111_1111);
+    let value = self.parse_value(data_field)?;
+
+    Ok(DataRecord { storage_number, tariff, subunit, value, unit, exponent })
+  }
+
+  fn parse_value(&mut self, data_field: u8) -> Result<Value<'ud>, Error> {
+    if data_field == 0xD {
+      let (&len, rest) = self.remaining.split_first().ok_or(Error::Incomplete(None))?;
+      self.remaining = rest;
+
+      let len = len as usize;
+      if self.remaining.len() < len {
+        return Err(Error::Incomplete(None))
+      }
+
+      let (data, rest) = self.remaining.split_at(len);
+      self.remaining = rest;
+      return Ok(Value::LVar(data))
+    }
+
+    let len = match data_field {
+      0x0 => 0,
+      0x1 => 1,
+      0x2 => 2,
+      0x3 => 3,
+      0x4 | 0x5 => 4,
+      0x6 => 6,
+      0x7 => 8,
+      0x9 => 1,
+      0xA => 2,
+      0xB => 3,
+      0xC => 4,
+      _ => return Err(Error::InvalidFormat),
+    };
+
+    if self.remaining.len() < len {
+      return Err(Error::Incomplete(None))
+    }
+
+    let (data, rest) = self.remaining.split_at(len);
+    self.remaining = rest;
+
+    match data_field {
+      0x0 => Ok(Value::None),
+      0x5 => {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(data);
+        Ok(Value::Real(f32::from_bits(u32::from_le_bytes(bytes))))
+      },
+      0x9..=0xC => {
+        let mut value = 0u64;
+        for (i, &byte) in data.iter().enumerate() {
+          let lo = byte & 0xf;
+          let hi = (byte >> 4) & 0xf;
+          if lo > 9 || hi > 9 {
+            return Err(Error::InvalidFormat)
+          }
+          value += u64::from(lo) * 10u64.pow(2 * i as u32);
+          value += u64::from(hi) * 10u64.pow(2 * i as u32 + 1);
+        }
+        Ok(Value::Bcd(value))
+      },
+      _ => {
+        let mut value = 0i64;
+        for (i, &byte) in data.iter().enumerate() {
+          value |= i64::from(byte) << (8 * i);
+        }
+
+        // Sign-extend from the top bit of the most significant populated byte, since `data`
+        // may be shorter than 8 bytes and the loop above only ever sets bits within its width.
+        let sign_bits = 64 - 8 * data.len() as u32;
+        if sign_bits > 0 && sign_bits < 64 {
+          value = (value << sign_bits) >> sign_bits;
+        }
+
+        Ok(Value::Integer(value))
+      },
+    }
+  }
+}
+
+impl<'ud> Iterator for DataRecords<'ud> {
+  type Item = Result<DataRecord<'ud>, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None
+    }
+
+    // `0x0f`/`0x1f` mark the end of the data records (manufacturer-specific data follows).
+    // `0x2f` is idle filler: a single byte with no VIF/value that is skipped and ignored.
+    loop {
+      match self.remaining.first() {
+        None | Some(0x0f) | Some(0x1f) => {
+          self.done = true;
+          return None
+        },
+        Some(0x2f) => {
+          self.remaining = &self.remaining[1..];
+          continue
+        },
+        Some(_) => {
+          let record = self.parse_record();
+          if record.is_err() {
+            self.done = true;
+          }
+          return Some(record)
+        },
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn user_data(records: &[u8]) -> std::vec::Vec<u8> {
+    let mut user_data = std::vec![0u8; Header::LONG_LEN];
+    user_data.extend_from_slice(records);
+    user_data
+  }
+
+  #[test]
+  fn test_data_records_parse() {
+    let user_data = user_data(&[
+      0x04, 0x04, 0x01, 0x00, 0x00, 0x00, // DIF: 4-byte int, VIF: energy, value 1
+      0x01, 0x00, 0xff, // DIF: 1-byte int, VIF: energy, value -1
+      0x01, 0x13, 0x05, // DIF: 1-byte int, VIF: volume, value 5
+      0x0f, // end of data records
+    ]);
+
+    let mut records = DataRecords::parse(0x72, &user_data).unwrap();
+
+    let first = records.next().unwrap().unwrap();
+    assert_eq!(first.value, Value::Integer(1));
+    assert_eq!(first.unit, Unit::Energy);
+    assert_eq!(first.exponent, 1);
+
+    let second = records.next().unwrap().unwrap();
+    assert_eq!(second.value, Value::Integer(-1));
+    assert_eq!(second.unit, Unit::Energy);
+    assert_eq!(second.exponent, -3);
+
+    let third = records.next().unwrap().unwrap();
+    assert_eq!(third.value, Value::Integer(5));
+    assert_eq!(third.unit, Unit::Volume);
+    assert_eq!(third.exponent, -3);
+
+    assert!(records.next().is_none());
+  }
+
+  #[test]
+  fn test_data_records_dife_chain_is_bounded() {
+    let mut record = std::vec![0x81u8];
+    record.extend(std::iter::repeat_n(0x81u8, 8));
+    let user_data = user_data(&record);
+
+    let mut records = DataRecords::parse(0x72, &user_data).unwrap();
+    assert!(matches!(records.next(), Some(Err(Error::InvalidFormat))));
+  }
+
+  #[test]
+  fn test_data_records_skips_idle_filler() {
+    let user_data = user_data(&[
+      0x2f, // idle filler
+      0x01, 0x00, 0x01, // DIF: 1-byte int, VIF: energy, value 1
+      0x2f, // idle filler
+      0x0f, // end of data records
+    ]);
+
+    let mut records = DataRecords::parse(0x72, &user_data).unwrap();
+
+    let first = records.next().unwrap().unwrap();
+    assert_eq!(first.value, Value::Integer(1));
+    assert_eq!(first.unit, Unit::Energy);
+    assert_eq!(first.exponent, -3);
+
+    assert!(records.next().is_none());
+  }
+
+  #[test]
+  fn test_data_records_short_header() {
+    let mut user_data = std::vec![0u8; Header::SHORT_LEN];
+    user_data.extend_from_slice(&[
+      0x01, 0x00, 0x01, // DIF: 1-byte int, VIF: energy, value 1
+      0x0f, // end of data records
+    ]);
+
+    let mut records = DataRecords::parse(0x7a, &user_data).unwrap();
+    assert!(matches!(records.header(), Header::Short { .. }));
+
+    let first = records.next().unwrap().unwrap();
+    assert_eq!(first.value, Value::Integer(1));
+    assert_eq!(first.unit, Unit::Energy);
+
+    assert!(records.next().is_none());
+  }
+}
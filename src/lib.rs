@@ -8,22 +8,48 @@ use core::num::NonZeroUsize;
 #[derive(Debug)]
 pub enum Error {
   /// Telegram does not start with the expected character.
-  InvalidStartCharacter,
+  InvalidStartCharacter {
+    /// The byte that was found instead.
+    byte: u8,
+    /// Offset of `byte` within the input.
+    offset: usize,
+  },
   /// Telegram format is wrong.
   InvalidFormat,
   /// Telegram is incomplete.
   Incomplete(Option<NonZeroUsize>),
   /// Checksum does not match.
-  ChecksumMismatch,
+  ChecksumMismatch {
+    /// The checksum computed from the received bytes.
+    computed: u8,
+    /// The checksum byte actually received.
+    received: u8,
+  },
+  /// Control field does not map to a known [`Control`] variant.
+  UnknownControl(u8),
+  /// Buffer is too small to hold the encoded telegram.
+  BufferTooSmall,
+  /// `user_data` is longer than the 252 bytes a telegram's `L` field can encode.
+  UserDataTooLong,
+  /// Underlying transport failed to read or write.
+  Io,
+  /// Decryption failed in the AES backend, or the decrypted payload failed the `0x2f 0x2f`
+  /// verification marker check.
+  DecryptionFailed,
 }
 
 impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      Self::InvalidStartCharacter => write!(f, "invalid start character"),
+      Self::InvalidStartCharacter { byte, offset } => write!(f, "invalid start character {byte:#04x} at offset {offset}"),
       Self::InvalidFormat => write!(f, "invalid format"),
       Self::Incomplete(_) => write!(f, "incomplete"),
-      Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+      Self::ChecksumMismatch { computed, received } => write!(f, "checksum mismatch: computed {computed:#04x}, received {received:#04x}"),
+      Self::UnknownControl(byte) => write!(f, "unknown control field {byte:#04x}"),
+      Self::BufferTooSmall => write!(f, "buffer too small"),
+      Self::UserDataTooLong => write!(f, "user data longer than 252 bytes"),
+      Self::Io => write!(f, "I/O error"),
+      Self::DecryptionFailed => write!(f, "decryption failed"),
     }
   }
 }
@@ -49,3 +75,21 @@ pub use control::Control;
 
 mod telegram;
 pub use telegram::Telegram;
+
+mod client;
+pub use client::SyncClient;
+
+mod master;
+pub use master::MBusMaster;
+
+mod crypto;
+pub use crypto::Aes128Cbc;
+#[cfg(feature = "rustcrypto")]
+pub use crypto::RustCrypto;
+#[cfg(feature = "mbedtls")]
+pub use crypto::Mbedtls;
+#[cfg(feature = "openssl")]
+pub use crypto::OpenSsl;
+
+mod data_record;
+pub use data_record::{DataRecords, DataRecord, Header, Unit, Value};
@@ -0,0 +1,377 @@
+use crate::{Address, Control, Error, SyncClient, Telegram};
+
+#[cfg(feature = "std")]
+mod io {
+  pub(crate) use std::io::{Read, Write};
+
+  pub(crate) fn read_exact<T: Read>(transport: &mut T, buf: &mut [u8]) -> Result<(), crate::Error> {
+    transport.read_exact(buf).map_err(|_| crate::Error::Io)
+  }
+
+  pub(crate) fn write_all<T: Write>(transport: &mut T, buf: &[u8]) -> Result<(), crate::Error> {
+    transport.write_all(buf).map_err(|_| crate::Error::Io)
+  }
+}
+
+#[cfg(not(feature = "std"))]
+mod io {
+  pub(crate) use embedded_io::{Read, Write};
+
+  pub(crate) fn read_exact<T: Read>(transport: &mut T, buf: &mut [u8]) -> Result<(), crate::Error> {
+    transport.read_exact(buf).map_err(|_| crate::Error::Io)
+  }
+
+  pub(crate) fn write_all<T: Write>(transport: &mut T, buf: &[u8]) -> Result<(), crate::Error> {
+    transport.write_all(buf).map_err(|_| crate::Error::Io)
+  }
+}
+
+use io::{Read, Write};
+
+/// Default number of times a confirmed exchange is retried before giving up.
+const DEFAULT_RETRIES: u8 = 3;
+
+/// Maximum size of an M-Bus telegram on the wire.
+const MAX_TELEGRAM_LEN: usize = 261;
+
+/// An M-Bus master driving a bus of slaves over a byte transport `T`.
+///
+/// Maintains the per-address Frame Count Bit (FCB) required to detect retransmissions and
+/// retries confirmed exchanges up to `retries` times, re-sending with the same FCB on failure.
+#[derive(Debug)]
+pub struct MBusMaster<T> {
+  transport: T,
+  retries: u8,
+  fcb: [bool; 256],
+}
+
+impl<T> MBusMaster<T> {
+  /// Creates a new master around `transport`, retrying confirmed exchanges up to 3 times.
+  pub fn new(transport: T) -> Self {
+    Self::with_retries(transport, DEFAULT_RETRIES)
+  }
+
+  /// Creates a new master around `transport`, retrying confirmed exchanges up to `retries` times.
+  pub fn with_retries(transport: T, retries: u8) -> Self {
+    Self { transport, retries, fcb: [false; 256] }
+  }
+
+  fn fcb(&self, address: u8) -> bool {
+    self.fcb[address as usize]
+  }
+
+  fn toggle_fcb(&mut self, address: u8) {
+    let fcb = &mut self.fcb[address as usize];
+    *fcb = !*fcb;
+  }
+}
+
+impl<T: Read + Write> MBusMaster<T> {
+  fn send_telegram(&mut self, telegram: &Telegram<'_>) -> Result<(), Error> {
+    let mut buf = [0u8; MAX_TELEGRAM_LEN];
+    let len = telegram.write_to(&mut buf)?;
+    io::write_all(&mut self.transport, &buf[..len])
+  }
+
+  /// Reads bytes into `buf` one at a time until they form a complete telegram, returning the
+  /// number of bytes read.
+  fn read_telegram(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+
+    loop {
+      if len >= buf.len() {
+        return Err(Error::BufferTooSmall)
+      }
+
+      io::read_exact(&mut self.transport, &mut buf[len..len + 1])?;
+      len += 1;
+
+      match Telegram::parse(&buf[..len]) {
+        Ok(_) => return Ok(len),
+        Err(Error::Incomplete(_)) => continue,
+        Err(err) => return Err(err),
+      }
+    }
+  }
+
+  fn receive_telegram<'buf>(&mut self, buf: &'buf mut [u8]) -> Result<Telegram<'buf>, Error> {
+    let len = self.read_telegram(buf)?;
+    let (_, telegram) = Telegram::parse(&buf[..len])?;
+    Ok(telegram)
+  }
+
+  /// Retries a confirmed exchange up to `self.retries` additional times, re-sending `telegram`
+  /// unchanged each time, and returns the first successful result.
+  ///
+  /// Bails out immediately on a non-retryable error: a too-small caller-supplied buffer can
+  /// never succeed on a retry, so retrying it would just burn the retry budget on a guaranteed
+  /// failure.
+  fn retrying<R>(&mut self, telegram: &Telegram<'_>, mut exchange: impl FnMut(&mut Self) -> Result<R, Error>) -> Result<R, Error> {
+    let mut last_error = Error::Incomplete(None);
+
+    for _ in 0..=self.retries {
+      self.send_telegram(telegram)?;
+
+      match exchange(self) {
+        Ok(value) => return Ok(value),
+        Err(Error::BufferTooSmall) => return Err(Error::BufferTooSmall),
+        Err(err) => last_error = err,
+      }
+    }
+
+    Err(last_error)
+  }
+
+  /// Initializes `address`, sending `SndNke` and expecting a single-character acknowledgement.
+  ///
+  /// `SndNke` resets the slave's Frame Count Bit expectation, so this also resets the master's
+  /// cached FCB for `address` to stay in sync, e.g. across a reconnect.
+  pub fn init(&mut self, address: u8) -> Result<(), Error> {
+    self.fcb[address as usize] = false;
+
+    let telegram = Telegram::ShortFrame { control: Control::SndNke, address: Address::from(address) };
+
+    self.retrying(&telegram, |master| {
+      let mut ack = [0u8; MAX_TELEGRAM_LEN];
+      match master.receive_telegram(&mut ack)? {
+        Telegram::SingleCharacter => Ok(()),
+        _ => Err(Error::InvalidFormat),
+      }
+    })
+  }
+
+  /// Requests class 2 data from `address`, returning the slave's control field and user data.
+  ///
+  /// The returned [`Control::RspUd`] exposes the slave's ACD/DFC bits so callers can detect
+  /// pending class-1 data and flow-control stalls. On success the Frame Count Bit for `address`
+  /// is toggled for the next exchange; on failure the same FCB is reused for the retry.
+  pub fn request_ud2<'buf>(&mut self, address: u8, buf: &'buf mut [u8]) -> Result<(Control, &'buf [u8]), Error> {
+    let fcb = self.fcb(address);
+    let telegram = Telegram::ShortFrame { control: Control::ReqUd2 { fcb }, address: Address::from(address) };
+
+    let (control, user_data_start, user_data_len) = self.retrying(&telegram, |master| {
+      let len = master.read_telegram(buf)?;
+
+      match Telegram::parse(&buf[..len])? {
+        (_, Telegram::LongFrame { control: control @ Control::RspUd { .. }, user_data, .. }) => {
+          let user_data_start = user_data.as_ptr() as usize - buf.as_ptr() as usize;
+          Ok((control, user_data_start, user_data.len()))
+        },
+        _ => Err(Error::InvalidFormat),
+      }
+    })?;
+
+    self.toggle_fcb(address);
+
+    Ok((control, &buf[user_data_start..user_data_start + user_data_len]))
+  }
+
+  /// Sends `data` to `address` with the given control information, expecting an acknowledgement.
+  ///
+  /// On success the Frame Count Bit for `address` is toggled for the next exchange.
+  pub fn send_ud(&mut self, address: u8, control_information: u8, data: &[u8]) -> Result<(), Error> {
+    let fcb = self.fcb(address);
+    let telegram = Telegram::LongFrame { control: Control::SndUd { fcb }, address: Address::from(address), control_information, user_data: data };
+
+    self.retrying(&telegram, |master| {
+      let mut ack = [0u8; MAX_TELEGRAM_LEN];
+      match master.receive_telegram(&mut ack)? {
+        Telegram::SingleCharacter => {
+          master.toggle_fcb(address);
+          Ok(())
+        },
+        _ => Err(Error::InvalidFormat),
+      }
+    })
+  }
+}
+
+impl<T: Read + Write> SyncClient for MBusMaster<T> {
+  fn send(&mut self, telegram: &Telegram<'_>) -> Result<(), Error> {
+    self.send_telegram(telegram)
+  }
+
+  fn receive<'buf>(&mut self, buf: &'buf mut [u8]) -> Result<Telegram<'buf>, Error> {
+    self.receive_telegram(buf)
+  }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+  use std::collections::VecDeque;
+
+  use super::*;
+
+  struct MockTransport {
+    written: std::vec::Vec<u8>,
+    to_read: VecDeque<u8>,
+  }
+
+  impl std::io::Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      let mut read = 0;
+      while read < buf.len() {
+        match self.to_read.pop_front() {
+          Some(byte) => {
+            buf[read] = byte;
+            read += 1;
+          },
+          None => break,
+        }
+      }
+      Ok(read)
+    }
+  }
+
+  impl std::io::Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.written.extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_toggle_fcb() {
+    let mut master = MBusMaster::new(MockTransport { written: std::vec![], to_read: VecDeque::new() });
+
+    assert!(!master.fcb(5));
+    master.toggle_fcb(5);
+    assert!(master.fcb(5));
+    master.toggle_fcb(5);
+    assert!(!master.fcb(5));
+  }
+
+  #[test]
+  fn test_init_sends_snd_nke_and_awaits_ack() {
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::from(std::vec![0xe5]) };
+    let mut master = MBusMaster::new(transport);
+
+    master.init(5).unwrap();
+
+    assert_eq!(master.transport.written, std::vec![0x10, 0x40, 0x05, 0x45, 0x16]);
+  }
+
+  #[test]
+  fn test_init_resets_fcb() {
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::from(std::vec![0xe5]) };
+    let mut master = MBusMaster::new(transport);
+
+    master.toggle_fcb(5);
+    assert!(master.fcb(5));
+
+    master.init(5).unwrap();
+    assert!(!master.fcb(5));
+  }
+
+  #[test]
+  fn test_init_retries_on_missing_ack() {
+    // No bytes to read at all: every attempt fails reading the acknowledgement.
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::new() };
+    let mut master = MBusMaster::with_retries(transport, 2);
+
+    assert!(matches!(master.init(5), Err(Error::Io)));
+    // The initial send plus 2 retries.
+    assert_eq!(master.transport.written.len(), 3 * 5);
+  }
+
+  #[test]
+  fn test_request_ud2_returns_rsp_ud_and_toggles_fcb() {
+    let user_data = [0x01u8, 0x02, 0x03];
+    let response = Telegram::LongFrame {
+      control: Control::RspUd { acd: true, dfc: false },
+      address: Address::from(5),
+      control_information: 0x72,
+      user_data: &user_data,
+    };
+    let mut response_buf = [0u8; 32];
+    let len = response.write_to(&mut response_buf).unwrap();
+
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::from(response_buf[..len].to_vec()) };
+    let mut master = MBusMaster::new(transport);
+
+    let mut buf = [0u8; 32];
+    let (control, data) = master.request_ud2(5, &mut buf).unwrap();
+
+    assert!(matches!(control, Control::RspUd { acd: true, dfc: false }));
+    assert_eq!(data, &user_data);
+    // The FCB for the address is toggled after a successful exchange.
+    assert!(master.fcb(5));
+    // The request was sent with the FCB from before the toggle.
+    assert_eq!(master.transport.written, std::vec![0x10, 0x5a, 0x05, 0x5f, 0x16]);
+  }
+
+  #[test]
+  fn test_request_ud2_retries_with_same_fcb_on_failure() {
+    let user_data = [0x01u8, 0x02, 0x03];
+    let response = Telegram::LongFrame {
+      control: Control::RspUd { acd: false, dfc: false },
+      address: Address::from(5),
+      control_information: 0x72,
+      user_data: &user_data,
+    };
+    let mut response_buf = [0u8; 32];
+    let len = response.write_to(&mut response_buf).unwrap();
+
+    // The first attempt's response has a corrupted checksum, so it fails and is retried.
+    let mut corrupted = response_buf[..len].to_vec();
+    let checksum_offset = len - 2;
+    corrupted[checksum_offset] = corrupted[checksum_offset].wrapping_add(1);
+
+    let mut to_read = corrupted;
+    to_read.extend_from_slice(&response_buf[..len]);
+
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::from(to_read) };
+    let mut master = MBusMaster::with_retries(transport, 1);
+
+    let mut buf = [0u8; 32];
+    let (control, data) = master.request_ud2(5, &mut buf).unwrap();
+
+    assert!(matches!(control, Control::RspUd { acd: false, dfc: false }));
+    assert_eq!(data, &user_data);
+    assert!(master.fcb(5));
+
+    // Both attempts sent the identical request, still carrying the pre-exchange FCB.
+    let request = std::vec![0x10, 0x5a, 0x05, 0x5f, 0x16];
+    assert_eq!(master.transport.written, [request.clone(), request].concat());
+  }
+
+  #[test]
+  fn test_request_ud2_does_not_retry_buffer_too_small() {
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::new() };
+    let mut master = MBusMaster::with_retries(transport, 2);
+
+    let mut buf = [0u8; 0];
+    assert!(matches!(master.request_ud2(5, &mut buf), Err(Error::BufferTooSmall)));
+    // A too-small buffer can never succeed on retry, so only the initial send happens.
+    assert_eq!(master.transport.written.len(), 5);
+  }
+
+  #[test]
+  fn test_send_ud_awaits_ack_and_toggles_fcb() {
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::from(std::vec![0xe5]) };
+    let mut master = MBusMaster::new(transport);
+
+    master.send_ud(5, 0x51, &[0xaa, 0xbb]).unwrap();
+
+    assert!(master.fcb(5));
+  }
+
+  #[test]
+  fn test_send_ud_retries_with_same_fcb_on_missing_ack() {
+    let transport = MockTransport { written: std::vec![], to_read: VecDeque::new() };
+    let mut master = MBusMaster::with_retries(transport, 2);
+
+    assert!(matches!(master.send_ud(5, 0x51, &[0xaa, 0xbb]), Err(Error::Io)));
+    assert!(!master.fcb(5));
+
+    // Every attempt sent the identical request, still carrying the pre-exchange FCB.
+    let sent = &master.transport.written;
+    let request_len = sent.len() / 3;
+    assert_eq!(&sent[0..request_len], &sent[request_len..2 * request_len]);
+    assert_eq!(&sent[0..request_len], &sent[2 * request_len..3 * request_len]);
+  }
+}
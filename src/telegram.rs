@@ -6,10 +6,9 @@ use nom::{
   number::streaming::u8,
   bytes::streaming::{tag, take},
   combinator::cut,
-  sequence::tuple,
 };
 
-use super::{Error, Control, Address};
+use super::{Error, Control, Address, Aes128Cbc, DataRecords, Header};
 
 /// An M-Bus telegram.
 #[derive(Debug, Clone, PartialEq)]
@@ -38,32 +37,48 @@ impl<'ud> Telegram<'ud> {
   const LONG_START_CHAR: u8 = 0x68;
   const STOP_CHAR: u8 = 0x16;
 
-  fn map_error(nom_error: nom::Err<nom::error::Error<&[u8]>>, error: Error) -> nom::Err<Error> {
-    nom_error.map(|_| error)
+  /// Largest `user_data` that fits the single-byte `L` field alongside the 3-byte
+  /// control/address/control-information header.
+  const MAX_USER_DATA_LEN: usize = 252;
+
+  /// Maps a failed start-character/length tag match to an [`Error::InvalidStartCharacter`],
+  /// recording the offending byte and its offset from the start of the telegram.
+  fn invalid_start(original: &'ud [u8], input: &'ud [u8], nom_error: nom::Err<nom::error::Error<&'ud [u8]>>) -> nom::Err<Error> {
+    let offset = original.len() - input.len();
+    let byte = input.first().copied().unwrap_or(0);
+    nom_error.map(|_| Error::InvalidStartCharacter { byte, offset })
   }
 
   fn parse_single(input: &'ud [u8]) -> IResult<&'ud [u8], Self, Error> {
     let (input, _) = tag([Telegram::SINGLE_CHAR])(input)
-      .map_err(|err| Self::map_error(err, Error::InvalidStartCharacter))?;
+      .map_err(|err| Self::invalid_start(input, input, err))?;
     Ok((input, Self::SingleCharacter))
   }
 
-  fn parse_short(input: &'ud [u8]) -> IResult<&'ud [u8], Self, Error> {
-    let (input, _) = tag([Self::SHORT_START_CHAR])(input)
-      .map_err(|err| Self::map_error(err, Error::InvalidStartCharacter))?;
+  fn parse_short(original: &'ud [u8]) -> IResult<&'ud [u8], Self, Error> {
+    let (input, _) = tag([Self::SHORT_START_CHAR])(original)
+      .map_err(|err| Self::invalid_start(original, original, err))?;
     Self::parse_payload(input, 2)
   }
 
-  fn parse_long(input: &'ud [u8]) -> IResult<&'ud [u8], Self, Error> {
-    let start_char = tag([Self::LONG_START_CHAR]);
-    let (input, (_, payload_len, payload_len_check, _)) =
-      tuple((&start_char, u8, u8, &start_char))(input)
-        .map_err(|err| Self::map_error(err, Error::InvalidStartCharacter))?;
+  fn parse_long(original: &'ud [u8]) -> IResult<&'ud [u8], Self, Error> {
+    let (input, _) = tag([Self::LONG_START_CHAR])(original)
+      .map_err(|err| Self::invalid_start(original, original, err))?;
+
+    let (input, payload_len) = u8(input)
+      .map_err(|err| Self::invalid_start(original, input, err))?;
+
+    let check_offset = original.len() - input.len();
+    let (input, payload_len_check) = u8(input)
+      .map_err(|err| Self::invalid_start(original, input, err))?;
 
     if payload_len != payload_len_check {
-      return Err(nom::Err::Error(Error::InvalidStartCharacter))
+      return Err(nom::Err::Error(Error::InvalidStartCharacter { byte: payload_len_check, offset: check_offset }))
     }
 
+    let (input, _) = tag([Self::LONG_START_CHAR])(input)
+      .map_err(|err| Self::invalid_start(original, input, err))?;
+
     Self::parse_payload(input, payload_len.into())
   }
 
@@ -76,9 +91,13 @@ impl<'ud> Telegram<'ud> {
       Ok((input, value))
     };
 
+    if payload_len < 2 {
+      return Err(nom::Err::Failure(Error::InvalidFormat))
+    }
+
     let (input, control) = checksummed_u8(input)?;
     let control = Control::try_from(control)
-      .map_err(|_| nom::Err::Failure(Error::InvalidFormat))?;
+      .map_err(|byte| nom::Err::Failure(Error::UnknownControl(byte)))?;
     payload_len -= 1;
 
     let (input, address) = checksummed_u8(input)?;
@@ -103,11 +122,11 @@ impl<'ud> Telegram<'ud> {
     let (input, _stop_char) = cut(tag([Self::STOP_CHAR]))(input)?;
 
     if calculated_checksum != checksum {
-      return Err(nom::Err::Failure(Error::ChecksumMismatch))
+      return Err(nom::Err::Failure(Error::ChecksumMismatch { computed: calculated_checksum, received: checksum }))
     }
 
     if let Some(control_information) = control_information {
-      if payload.len() > 0 {
+      if !payload.is_empty() {
         Ok((input, Self::LongFrame { control, address, control_information, user_data: payload }))
       } else {
         Ok((input, Self::ControlFrame { control, address, control_information }))
@@ -130,6 +149,148 @@ impl<'ud> Telegram<'ud> {
       })
       .finish()
   }
+
+  /// Returns the number of bytes this telegram encodes to.
+  fn encoded_len(&self) -> usize {
+    match self {
+      Self::SingleCharacter => 1,
+      Self::ShortFrame { .. } => 5,
+      Self::ControlFrame { .. } => 9,
+      Self::LongFrame { user_data, .. } => 9 + user_data.len(),
+    }
+  }
+
+  /// Writes the encoded telegram into `buf`, returning the number of bytes written.
+  ///
+  /// Returns [`Error::UserDataTooLong`] if a [`Self::LongFrame`]'s `user_data` is longer than
+  /// 252 bytes, or [`Error::BufferTooSmall`] if `buf` is not large enough to hold the telegram.
+  pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, Error> {
+    if let Self::LongFrame { user_data, .. } = self {
+      if user_data.len() > Self::MAX_USER_DATA_LEN {
+        return Err(Error::UserDataTooLong)
+      }
+    }
+
+    let len = self.encoded_len();
+
+    if buf.len() < len {
+      return Err(Error::BufferTooSmall)
+    }
+
+    match self {
+      Self::SingleCharacter => {
+        buf[0] = Self::SINGLE_CHAR;
+      },
+      Self::ShortFrame { control, address } => {
+        let control = u8::from(*control);
+        let address = u8::from(*address);
+        let checksum = control.wrapping_add(address);
+
+        buf[0] = Self::SHORT_START_CHAR;
+        buf[1] = control;
+        buf[2] = address;
+        buf[3] = checksum;
+        buf[4] = Self::STOP_CHAR;
+      },
+      Self::ControlFrame { control, address, control_information } => {
+        Self::write_long_frame(buf, *control, *address, *control_information, &[]);
+      },
+      Self::LongFrame { control, address, control_information, user_data } => {
+        Self::write_long_frame(buf, *control, *address, *control_information, user_data);
+      },
+    }
+
+    Ok(len)
+  }
+
+  fn write_long_frame(buf: &mut [u8], control: Control, address: Address, control_information: u8, user_data: &[u8]) {
+    let control = u8::from(control);
+    let address = u8::from(address);
+    let payload_len = (3 + user_data.len()) as u8;
+
+    let mut checksum = control.wrapping_add(address).wrapping_add(control_information);
+    for &byte in user_data {
+      checksum = checksum.wrapping_add(byte);
+    }
+
+    buf[0] = Self::LONG_START_CHAR;
+    buf[1] = payload_len;
+    buf[2] = payload_len;
+    buf[3] = Self::LONG_START_CHAR;
+    buf[4] = control;
+    buf[5] = address;
+    buf[6] = control_information;
+    buf[7..7 + user_data.len()].copy_from_slice(user_data);
+    buf[7 + user_data.len()] = checksum;
+    buf[8 + user_data.len()] = Self::STOP_CHAR;
+  }
+
+  /// Encodes this telegram into a newly allocated [`Vec`].
+  ///
+  /// Returns [`Error::UserDataTooLong`] if a [`Self::LongFrame`]'s `user_data` is longer than
+  /// 252 bytes; the allocated buffer is otherwise always sized to fit the encoded telegram.
+  #[cfg(feature = "std")]
+  pub fn to_vec(&self) -> Result<std::vec::Vec<u8>, Error> {
+    let mut buf = std::vec![0u8; self.encoded_len()];
+    self.write_to(&mut buf)?;
+    Ok(buf)
+  }
+
+  /// Decrypts the Security Mode 5 `user_data` of a [`Telegram::LongFrame`] into `buf`.
+  ///
+  /// The first 12 bytes of `user_data` are the long header (identification number, manufacturer,
+  /// version, device type, access number, status and signature); all of its fields except status
+  /// and signature are used to build the AES initialization vector per the Mode 5 convention,
+  /// then the remaining ciphertext is decrypted with `cipher` using `key`. A telegram whose
+  /// control information selects the short (`0x7A`) header yields [`Error::InvalidFormat`].
+  /// Returns [`Error::DecryptionFailed`] if the decrypted plaintext does
+  /// not start with the `0x2f 0x2f` verification marker; that marker and any trailing `0x2f`
+  /// idle-filler bytes are stripped from the returned slice.
+  pub fn decrypt<'buf, C: Aes128Cbc>(&self, key: &[u8; 16], cipher: &C, buf: &'buf mut [u8]) -> Result<&'buf [u8], Error> {
+    let Self::LongFrame { control_information, user_data, .. } = self else {
+      return Err(Error::InvalidFormat)
+    };
+
+    let (header, ciphertext) = Header::parse(*control_information, user_data)?;
+
+    // The IV needs the secondary address fields, which only the long header carries; a short
+    // (`0x7A`) header's address is known from the link layer instead, which this type doesn't
+    // track, so there's nothing to build the IV from.
+    let Header::Long { identification_number, manufacturer, version, device_type, access_number, .. } = header else {
+      return Err(Error::InvalidFormat)
+    };
+
+    if buf.len() < ciphertext.len() {
+      return Err(Error::BufferTooSmall)
+    }
+
+    let mut iv = [0u8; 16];
+    iv[0..2].copy_from_slice(&manufacturer);
+    iv[2..6].copy_from_slice(&identification_number);
+    iv[6] = version;
+    iv[7] = device_type;
+    iv[8..16].fill(access_number);
+
+    let plaintext = &mut buf[..ciphertext.len()];
+    plaintext.copy_from_slice(ciphertext);
+    cipher.decrypt(key, &iv, plaintext)?;
+
+    if plaintext.get(0..2) != Some(&[0x2f, 0x2f]) {
+      return Err(Error::DecryptionFailed)
+    }
+
+    let end = plaintext.iter().rposition(|&byte| byte != 0x2f).map_or(0, |pos| pos + 1).max(2);
+    Ok(&buf[2..end])
+  }
+
+  /// Parses the `user_data` of a [`Telegram::LongFrame`] into its fixed header and data records.
+  pub fn data_records(&self) -> Result<DataRecords<'ud>, Error> {
+    let Self::LongFrame { control_information, user_data, .. } = self else {
+      return Err(Error::InvalidFormat)
+    };
+
+    DataRecords::parse(*control_information, user_data)
+  }
 }
 
 #[cfg(test)]
@@ -207,4 +368,191 @@ mod test {
       },
     ])
   }
+
+  #[test]
+  fn test_write_to_roundtrip() {
+    let telegrams = [
+      Telegram::SingleCharacter,
+      Telegram::ShortFrame { control: Control::ReqUd2 { fcb: true }, address: Address::Configured(5) },
+      Telegram::ControlFrame { control: Control::SndUd { fcb: false }, address: Address::Configured(1), control_information: 0x51 },
+      Telegram::LongFrame { control: Control::RspUd { acd: true, dfc: false }, address: Address::Configured(1), control_information: 0x72, user_data: &[0x01, 0x02, 0x03] },
+    ];
+
+    for telegram in telegrams {
+      let mut buf = [0u8; 16];
+      let len = telegram.write_to(&mut buf).unwrap();
+
+      let (rest, parsed) = Telegram::parse(&buf[..len]).unwrap();
+      assert!(rest.is_empty());
+      assert_eq!(parsed, telegram);
+    }
+  }
+
+  #[test]
+  fn test_write_to_buffer_too_small() {
+    let (_, telegram) = Telegram::parse(&TELEGRAMS[..]).unwrap();
+
+    let mut buf = [0u8; 1];
+    assert!(matches!(telegram.write_to(&mut buf), Err(Error::BufferTooSmall)));
+  }
+
+  #[test]
+  fn test_write_to_user_data_too_large() {
+    let user_data = [0u8; Telegram::MAX_USER_DATA_LEN + 1];
+    let telegram = Telegram::LongFrame { control: Control::SndUd { fcb: false }, address: Address::Configured(1), control_information: 0x51, user_data: &user_data };
+
+    let mut buf = [0u8; 512];
+    assert!(matches!(telegram.write_to(&mut buf), Err(Error::UserDataTooLong)));
+  }
+
+  #[test]
+  fn test_to_vec_user_data_too_large() {
+    let user_data = [0u8; Telegram::MAX_USER_DATA_LEN + 1];
+    let telegram = Telegram::LongFrame { control: Control::SndUd { fcb: false }, address: Address::Configured(1), control_information: 0x51, user_data: &user_data };
+
+    assert!(matches!(telegram.to_vec(), Err(Error::UserDataTooLong)));
+  }
+
+  #[test]
+  fn test_parse_invalid_start_character() {
+    let err = Telegram::parse(&[0xff]).unwrap_err();
+    assert!(matches!(err, Error::InvalidStartCharacter { byte: 0xff, offset: 0 }));
+  }
+
+  #[test]
+  fn test_parse_checksum_mismatch() {
+    let telegram = Telegram::ShortFrame { control: Control::SndNke, address: Address::Configured(1) };
+    let mut buf = [0u8; 5];
+    telegram.write_to(&mut buf).unwrap();
+
+    buf[3] = buf[3].wrapping_add(1); // corrupt the checksum byte
+
+    let err = Telegram::parse(&buf).unwrap_err();
+    assert!(matches!(err, Error::ChecksumMismatch { .. }));
+  }
+
+  #[test]
+  fn test_parse_unknown_control() {
+    let telegram = Telegram::ShortFrame { control: Control::SndNke, address: Address::Configured(1) };
+    let mut buf = [0u8; 5];
+    telegram.write_to(&mut buf).unwrap();
+
+    buf[1] = 0xff; // not a recognized control byte
+
+    let err = Telegram::parse(&buf).unwrap_err();
+    assert!(matches!(err, Error::UnknownControl(0xff)));
+  }
+
+  #[test]
+  fn test_parse_long_frame_payload_too_short() {
+    // `L = 0x00`: too short to hold the control/address bytes `parse_payload` requires.
+    let err = Telegram::parse(&[0x68, 0x00, 0x00, 0x68, 0x40, 0x40, 0x16]).unwrap_err();
+    assert!(matches!(err, Error::InvalidFormat));
+
+    // `L = 0x01`: still one byte short.
+    let err = Telegram::parse(&[0x68, 0x01, 0x01, 0x68, 0x40, 0x40, 0x16]).unwrap_err();
+    assert!(matches!(err, Error::InvalidFormat));
+  }
+
+  #[test]
+  #[cfg(feature = "rustcrypto")]
+  fn test_decrypt_round_trip() {
+    use cbc::cipher::{BlockEncryptMut, KeyIvInit, block_padding::NoPadding};
+
+    use crate::RustCrypto;
+
+    let key = [0x11u8; 16];
+    let header = [
+      0x01, 0x02, 0x03, 0x04, // identification number
+      0x05, 0x06, // manufacturer
+      0x07, // version
+      0x08, // device type
+      0x09, // access number
+      0x00, // status
+      0x00, 0x00, // signature
+    ];
+
+    let mut iv = [0u8; 16];
+    iv[0..2].copy_from_slice(&header[4..6]);
+    iv[2..6].copy_from_slice(&header[0..4]);
+    iv[6] = header[6];
+    iv[7] = header[7];
+    iv[8..16].fill(header[8]);
+
+    // Decryption-verification marker followed by payload bytes and trailing `0x2f` filler.
+    let plaintext = [0x2f, 0x2f, 0x01, 0x02, 0x03, 0x04, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f, 0x2f];
+    let mut ciphertext = plaintext;
+    let len = cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+      .encrypt_padded_mut::<NoPadding>(&mut ciphertext, plaintext.len())
+      .unwrap()
+      .len();
+
+    let mut user_data = std::vec::Vec::from(header);
+    user_data.extend_from_slice(&ciphertext[..len]);
+
+    let telegram = Telegram::LongFrame {
+      control: Control::RspUd { acd: false, dfc: false },
+      address: Address::Configured(1),
+      control_information: 0x72,
+      user_data: &user_data,
+    };
+
+    let mut buf = [0u8; 16];
+    let decrypted = telegram.decrypt(&key, &RustCrypto, &mut buf).unwrap();
+    assert_eq!(decrypted, &[0x01, 0x02, 0x03, 0x04]);
+  }
+
+  #[test]
+  #[cfg(feature = "rustcrypto")]
+  fn test_decrypt_all_filler() {
+    use cbc::cipher::{BlockEncryptMut, KeyIvInit, block_padding::NoPadding};
+
+    use crate::RustCrypto;
+
+    let key = [0x11u8; 16];
+    let header = [0u8; 12];
+    let iv = [0u8; 16];
+
+    // Nothing but the verification marker and filler: no application data survives.
+    let plaintext = [0x2fu8; 16];
+    let mut ciphertext = plaintext;
+    let len = cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+      .encrypt_padded_mut::<NoPadding>(&mut ciphertext, plaintext.len())
+      .unwrap()
+      .len();
+
+    let mut user_data = std::vec::Vec::from(header);
+    user_data.extend_from_slice(&ciphertext[..len]);
+
+    let telegram = Telegram::LongFrame {
+      control: Control::RspUd { acd: false, dfc: false },
+      address: Address::Configured(1),
+      control_information: 0x72,
+      user_data: &user_data,
+    };
+
+    let mut buf = [0u8; 16];
+    let decrypted = telegram.decrypt(&key, &RustCrypto, &mut buf).unwrap();
+    assert_eq!(decrypted, &[] as &[u8]);
+  }
+
+  #[test]
+  #[cfg(feature = "rustcrypto")]
+  fn test_decrypt_buffer_too_small() {
+    use crate::RustCrypto;
+
+    let key = [0x11u8; 16];
+    let mut user_data = std::vec![0u8; Header::LONG_LEN];
+    user_data.extend_from_slice(&[0u8; 16]); // one block of ciphertext
+
+    let telegram = Telegram::LongFrame {
+      control: Control::RspUd { acd: false, dfc: false },
+      address: Address::Configured(1),
+      control_information: 0x72,
+      user_data: &user_data,
+    };
+
+    let mut buf = [0u8; 8];
+    assert!(matches!(telegram.decrypt(&key, &RustCrypto, &mut buf), Err(Error::BufferTooSmall)));
+  }
 }